@@ -0,0 +1,125 @@
+//! Counts the heap allocations of a connect through the boxed `Service` future and through
+//! `connect_once`, which awaits the handshake directly
+//!
+//! Run with `cargo bench --bench allocations`.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    future::{self, Future},
+    io,
+    pin::Pin,
+    sync::atomic::{AtomicUsize, Ordering},
+    task::{Context, Poll},
+};
+
+use http::Uri;
+use hyper_socks2::SocksConnector;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tower_service::Service;
+
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+const CONNECTS: usize = 10_000;
+
+/// A proxy connection answering with the method selection and CONNECT replies and
+/// discarding what it's sent, so it allocates nothing itself
+struct AnsweredProxy(&'static [u8]);
+
+impl AnsweredProxy {
+    fn new() -> Self {
+        Self(&[0x05, 0x00, 0x05, 0x00, 0x00, 0x01, 0, 0, 0, 0, 0, 0])
+    }
+}
+
+impl AsyncRead for AnsweredProxy {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let len = self.0.len().min(buf.remaining());
+        buf.put_slice(&self.0[..len]);
+        self.0 = &self.0[len..];
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncWrite for AnsweredProxy {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// The average number of allocations of the connects made by `connect`
+async fn allocations_per_connect<F, Fut>(mut connect: F) -> f64
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = ()>,
+{
+    // Warm up lazily initialized state
+    connect().await;
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    for _ in 0..CONNECTS {
+        connect().await;
+    }
+    (ALLOCATIONS.load(Ordering::Relaxed) - before) as f64 / CONNECTS as f64
+}
+
+fn main() {
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    let socks =
+        SocksConnector::with_stream_fn(Uri::from_static("socks5h://127.0.0.1:1080"), None, |_| {
+            future::ready(Ok(AnsweredProxy::new()))
+        });
+    let target = Uri::from_static("http://example.com");
+
+    rt.block_on(async {
+        let service = allocations_per_connect(|| {
+            let mut socks = socks.clone();
+            let target = target.clone();
+            async move {
+                socks.call(target).await.unwrap();
+            }
+        })
+        .await;
+        let direct = allocations_per_connect(|| {
+            let socks = socks.clone();
+            let target = target.clone();
+            async move {
+                socks.connect_once(target).await.unwrap();
+            }
+        })
+        .await;
+        println!("allocations per connect: Service::call {service:.1}, connect_once {direct:.1}");
+    });
+}
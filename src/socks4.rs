@@ -0,0 +1,65 @@
+//! SOCKS4 and SOCKS4a CONNECT, see <https://www.openssh.com/txt/socks4.protocol>
+//! and <https://www.openssh.com/txt/socks4a.protocol>
+
+use crate::ConnectPhase;
+use async_socks5::{AddrKind, Error, UnsuccessfulReply};
+use std::net::{Ipv4Addr, SocketAddr};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Request a connection to `target` and return the bound address from the reply
+///
+/// Domains are passed to the proxy unresolved as SOCKS4a does,
+/// so resolve them beforehand for plain SOCKS4 proxies.
+pub(crate) async fn connect<S>(
+    stream: &mut S,
+    target: &AddrKind,
+    user_id: &str,
+) -> Result<AddrKind, Error>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    crate::enter_phase(ConnectPhase::Connect);
+    let mut request = vec![0x04, 0x01];
+    match target {
+        AddrKind::Ip(SocketAddr::V4(addr)) => {
+            request.extend_from_slice(&addr.port().to_be_bytes());
+            request.extend_from_slice(&addr.ip().octets());
+            request.extend_from_slice(user_id.as_bytes());
+            request.push(0);
+        }
+        AddrKind::Domain(domain, port) => {
+            request.extend_from_slice(&port.to_be_bytes());
+            // `0.0.0.x` with non-zero `x` tells the proxy a domain follows the user id
+            request.extend_from_slice(&[0, 0, 0, 1]);
+            request.extend_from_slice(user_id.as_bytes());
+            request.push(0);
+            request.extend_from_slice(domain.as_bytes());
+            request.push(0);
+        }
+        AddrKind::Ip(SocketAddr::V6(_)) => {
+            return Err(Error::Response(UnsuccessfulReply::AddressTypeNotSupported))
+        }
+    }
+    stream.write_all(&request).await?;
+    stream.flush().await?;
+
+    let mut reply = [0; 8];
+    stream.read_exact(&mut reply).await?;
+    if reply[0] != 0x00 {
+        return Err(Error::InvalidVersion(reply[0]));
+    }
+    match reply[1] {
+        0x5a => {}
+        0x5b => return Err(Error::Response(UnsuccessfulReply::GeneralFailure)),
+        // identd is unreachable or reported another user id
+        0x5c | 0x5d => {
+            return Err(Error::Response(
+                UnsuccessfulReply::ConnectionNotAllowedByRules,
+            ))
+        }
+        code => return Err(Error::Response(UnsuccessfulReply::Unassigned(code))),
+    }
+    let port = u16::from_be_bytes([reply[2], reply[3]]);
+    let ip = Ipv4Addr::new(reply[4], reply[5], reply[6], reply[7]);
+    Ok(AddrKind::Ip(SocketAddr::from((ip, port))))
+}
@@ -0,0 +1,205 @@
+//! SOCKS5 requests offering an explicit set of authentication methods,
+//! see <https://tools.ietf.org/html/rfc1928> and <https://tools.ietf.org/html/rfc1929>
+
+use crate::ConnectPhase;
+use async_socks5::{AddrKind, Auth, AuthMethod, Error, StringKind, UnsuccessfulReply};
+use std::{
+    collections::BTreeSet,
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr},
+};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Offer `methods` to the proxy, authenticate with `auth` if it selects username/password
+/// and request a connection to `target`, returning the selected method and the bound
+/// address from the reply. Reply codes in `accepted` are taken as success like `0x00`
+pub(crate) async fn connect<S>(
+    stream: &mut S,
+    target: &AddrKind,
+    methods: &[u8],
+    auth: Option<&Auth>,
+    accepted: &BTreeSet<u8>,
+) -> Result<(AuthMethod, AddrKind), Error>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let method = greet(stream, methods, auth).await?;
+    request(stream, 0x01, target).await?;
+    let reply = read_reply_code(stream).await?;
+    if reply != 0x00 && !accepted.contains(&reply) {
+        return Err(Error::Response(unsuccessful_reply(reply)));
+    }
+    Ok((method, read_bound_addr(stream).await?))
+}
+
+/// Like [`connect`] with any `command`, returning the reply code and the bound address
+/// without interpreting them
+pub(crate) async fn command<S>(
+    stream: &mut S,
+    command: u8,
+    target: &AddrKind,
+    methods: &[u8],
+    auth: Option<&Auth>,
+) -> Result<(u8, AddrKind), Error>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    greet(stream, methods, auth).await?;
+    request(stream, command, target).await?;
+    let reply = read_reply_code(stream).await?;
+    Ok((reply, read_bound_addr(stream).await?))
+}
+
+/// Offer `methods` and authenticate with `auth` if the proxy selects username/password,
+/// returning the selected method
+async fn greet<S>(stream: &mut S, methods: &[u8], auth: Option<&Auth>) -> Result<AuthMethod, Error>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    crate::enter_phase(ConnectPhase::Greeting);
+    let mut greeting = vec![0x05, methods.len() as u8];
+    greeting.extend_from_slice(methods);
+    stream.write_all(&greeting).await?;
+    stream.flush().await?;
+
+    let mut selection = [0; 2];
+    stream.read_exact(&mut selection).await?;
+    check_version(selection[0])?;
+    match (selection[1], auth) {
+        (0xff, _) => return Err(Error::NoAcceptableMethods),
+        (method, _) if !methods.contains(&method) => {
+            return Err(Error::InvalidAuthMethod(auth_method(method)))
+        }
+        (0x02, Some(auth)) => subnegotiate(stream, auth).await?,
+        (0x00, _) => {}
+        (method, _) => return Err(Error::InvalidAuthMethod(auth_method(method))),
+    }
+    Ok(auth_method(selection[1]))
+}
+
+async fn request<S>(stream: &mut S, command: u8, target: &AddrKind) -> Result<(), Error>
+where
+    S: AsyncWrite + Unpin,
+{
+    crate::enter_phase(ConnectPhase::Connect);
+    let mut request = vec![0x05, command, 0x00];
+    crate::encode_addr(target, &mut request)
+        .map_err(|_| Error::TooLongString(StringKind::Domain))?;
+    stream.write_all(&request).await?;
+    stream.flush().await?;
+    Ok(())
+}
+
+/// Read the version and the reply code, leaving the rest of a failed reply unread
+/// as some proxies close right after the code
+async fn read_reply_code<S>(stream: &mut S) -> Result<u8, Error>
+where
+    S: AsyncRead + Unpin,
+{
+    let mut reply = [0; 2];
+    stream.read_exact(&mut reply).await?;
+    check_version(reply[0])?;
+    Ok(reply[1])
+}
+
+/// A SOCKS4 proxy answers with a different version, reported like `async_socks5` does
+fn check_version(version: u8) -> Result<(), Error> {
+    match version {
+        0x05 => Ok(()),
+        0x04 => Err(Error::WrongVersion),
+        version => Err(Error::InvalidVersion(version)),
+    }
+}
+
+/// Read the rest of the reply after the code
+async fn read_bound_addr<S>(stream: &mut S) -> Result<AddrKind, Error>
+where
+    S: AsyncRead + Unpin,
+{
+    let mut reply = [0; 2];
+    stream.read_exact(&mut reply).await?;
+    if reply[0] != 0x00 {
+        return Err(Error::InvalidReserved(reply[0]));
+    }
+    read_addr(stream, reply[1]).await
+}
+
+fn unsuccessful_reply(code: u8) -> UnsuccessfulReply {
+    match code {
+        0x01 => UnsuccessfulReply::GeneralFailure,
+        0x02 => UnsuccessfulReply::ConnectionNotAllowedByRules,
+        0x03 => UnsuccessfulReply::NetworkUnreachable,
+        0x04 => UnsuccessfulReply::HostUnreachable,
+        0x05 => UnsuccessfulReply::ConnectionRefused,
+        0x06 => UnsuccessfulReply::TtlExpired,
+        0x07 => UnsuccessfulReply::CommandNotSupported,
+        0x08 => UnsuccessfulReply::AddressTypeNotSupported,
+        code => UnsuccessfulReply::Unassigned(code),
+    }
+}
+
+/// Username/password authentication of RFC 1929
+async fn subnegotiate<S>(stream: &mut S, auth: &Auth) -> Result<(), Error>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    crate::enter_phase(ConnectPhase::Auth);
+    let username = u8::try_from(auth.username.len())
+        .map_err(|_| Error::TooLongString(StringKind::Username))?;
+    let password = u8::try_from(auth.password.len())
+        .map_err(|_| Error::TooLongString(StringKind::Password))?;
+    let mut request = vec![0x01, username];
+    request.extend_from_slice(auth.username.as_bytes());
+    request.push(password);
+    request.extend_from_slice(auth.password.as_bytes());
+    stream.write_all(&request).await?;
+    stream.flush().await?;
+
+    let mut reply = [0; 2];
+    stream.read_exact(&mut reply).await?;
+    if reply[0] != 0x01 {
+        return Err(Error::InvalidAuthSubnegotiation(reply[0]));
+    }
+    if reply[1] != 0x00 {
+        return Err(Error::InvalidAuthStatus(reply[1]));
+    }
+    Ok(())
+}
+
+async fn read_addr<S>(stream: &mut S, atyp: u8) -> Result<AddrKind, Error>
+where
+    S: AsyncRead + Unpin,
+{
+    let addr = match atyp {
+        0x01 => {
+            let mut ip = [0; 4];
+            stream.read_exact(&mut ip).await?;
+            let port = stream.read_u16().await?;
+            AddrKind::Ip(SocketAddr::from((Ipv4Addr::from(ip), port)))
+        }
+        0x04 => {
+            let mut ip = [0; 16];
+            stream.read_exact(&mut ip).await?;
+            let port = stream.read_u16().await?;
+            AddrKind::Ip(SocketAddr::from((Ipv6Addr::from(ip), port)))
+        }
+        0x03 => {
+            let len = stream.read_u8().await?;
+            let mut domain = vec![0; len as usize];
+            stream.read_exact(&mut domain).await?;
+            let port = stream.read_u16().await?;
+            AddrKind::Domain(String::from_utf8(domain)?, port)
+        }
+        atyp => return Err(Error::InvalidAtyp(atyp)),
+    };
+    Ok(addr)
+}
+
+fn auth_method(method: u8) -> AuthMethod {
+    match method {
+        0x00 => AuthMethod::None,
+        0x01 => AuthMethod::GssApi,
+        0x02 => AuthMethod::UsernamePassword,
+        0x03..=0x7f => AuthMethod::IanaReserved(method),
+        _ => AuthMethod::Private(method),
+    }
+}
@@ -0,0 +1,6 @@
+#[cfg(not(any(feature = "tls", feature = "rustls")))]
+#[test]
+fn with_tls_without_features() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/with_tls_no_features.rs");
+}
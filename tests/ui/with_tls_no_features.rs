@@ -0,0 +1,12 @@
+use hyper::Uri;
+use hyper_socks2::SocksConnector;
+use hyper_util::client::legacy::connect::HttpConnector;
+
+fn main() {
+    let proxy = SocksConnector::new(
+        Uri::from_static("socks5://127.0.0.1:1080"),
+        None,
+        HttpConnector::new(),
+    );
+    let _ = proxy.with_tls();
+}